@@ -9,7 +9,7 @@ use std::collections::{BTreeMap, BTreeSet, BinaryHeap};
 use std::fmt::{Display, Debug, Formatter};
 use std::cmp::Ordering;
 use float_ord::FloatOrd;
-use cpython::{PyResult, PyTuple, ToPyObject, ObjectProtocol, Python, PyList, PyObject, PyDict, PyClone};
+use cpython::{PyResult, PyErr, PyTuple, ToPyObject, ObjectProtocol, Python, PyList, PyObject, PyDict, PyClone, exc};
 use smallvec::{SmallVec, smallvec};
 
 pub type SVec<T, const N: usize = 1> = SmallVec<[T; N]>;
@@ -17,6 +17,48 @@ pub type SVec<T, const N: usize = 1> = SmallVec<[T; N]>;
 type Dimension = u8;
 type Shape = SVec<usize, 4>;
 
+/// The element type of a tensor, mirroring tract-data's `DatumType`: just enough
+/// variants to price communication by byte width instead of assuming fp32 everywhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DatumType {
+    F16,
+    BF16,
+    F32,
+    F64,
+    I8,
+    I16,
+    I32,
+    I64,
+    Bool,
+}
+
+impl DatumType {
+    fn size_of(&self) -> usize {
+        match self {
+            DatumType::F16 | DatumType::BF16 | DatumType::I16 => 2,
+            DatumType::F32 | DatumType::I32 => 4,
+            DatumType::F64 | DatumType::I64 => 8,
+            DatumType::I8 | DatumType::Bool => 1,
+        }
+    }
+
+    fn from_py(py: Python, py_dtype: &PyObject) -> PyResult<DatumType> {
+        let repr = py_dtype.str(py)?.to_string(py)?.into_owned();
+        Ok(match &repr[..] {
+            "torch.float16" => DatumType::F16,
+            "torch.bfloat16" => DatumType::BF16,
+            "torch.float32" => DatumType::F32,
+            "torch.float64" => DatumType::F64,
+            "torch.int8" => DatumType::I8,
+            "torch.int16" => DatumType::I16,
+            "torch.int32" => DatumType::I32,
+            "torch.int64" => DatumType::I64,
+            "torch.bool" => DatumType::Bool,
+            _ => panic!("unsupported dtype {repr}")
+        })
+    }
+}
+
 static CTRLC_TRAPPED: AtomicBool = AtomicBool::new(false);
 static CTRLC_RECEIVED: AtomicBool = AtomicBool::new(false);
 
@@ -30,19 +72,15 @@ cpython::py_module_initializer!(hetspmd, |py, m| {
     #[allow(clippy::manual_strip)]
     m.add(py, "main", cpython::py_fn!(py, main(py_graph_module: PyObject, py_config: PyDict) -> PyResult<PyList> {
         let py_input_shape_dict = py_config.get_item(py, "input_shape").unwrap();
-        let (rgraph, module_info) = load_fx_graph(py, py_graph_module.clone_ref(py), py_input_shape_dict)?;
+        let (mut rgraph, mut module_info) = load_fx_graph(py, py_graph_module.clone_ref(py), py_input_shape_dict)?;
 
         // eprintln!("graph: {rgraph:#?}");
         // eprintln!("module_info: {module_info:#?}");
 
-        let mut triples = analyze_rgraph(&rgraph, &module_info);
+        let mut triples = analyze_rgraph(&mut rgraph, &mut module_info);
         let mut default_properties = vec![];
 
-        // heuristics::compute_only_once(&mut triples, &mut default_properties, &rgraph);
-        // heuristics::ordered_communication(&mut triples, &mut default_properties, &rgraph);
-        // heuristics::fuse_communication_forward(&mut triples, &mut default_properties, &rgraph);
-        // heuristics::ordered_placeholder_chain(&mut triples, &mut default_properties, &rgraph);
-        // heuristics::ordered_get_attr_chain(&mut triples, &mut default_properties, &rgraph);
+        heuristics::run_passes(&heuristics::default_passes(), &mut triples, &mut default_properties, &rgraph);
 
         // for triple in triples.iter() {
         //     eprintln!("{triple}");
@@ -50,10 +88,14 @@ cpython::py_module_initializer!(hetspmd, |py, m| {
 
         let cluster_info = ClusterInfo {
             device_flops: vec![4139214925014.; 4],
-            all_reduce_bandwidth: 611692856.,
-            all_gather_bandwidth: 1224592728.,
-            reduce_scatter_bandwidth: 1130230706.,
-            all_to_all_bandwidth: 10701240728.
+            all_gather_alpha: 1e-5,
+            all_gather_beta: 1. / 1224592728.,
+            all_reduce_alpha: 1e-5,
+            all_reduce_beta: 1. / 611692856.,
+            reduce_scatter_alpha: 1e-5,
+            reduce_scatter_beta: 1. / 1130230706.,
+            all_to_all_alpha: 1e-5,
+            all_to_all_beta: 1. / 10701240728.
         };
 
         let profiler = Profiler {
@@ -130,10 +172,21 @@ pub struct HoareTriple {
     post_conditions: SVec<Property>,
     negative_post_conditions: Vec<Property>,
     instruction: String, // for debugging purpose
+    kind: HoareTripleKind, // what this triple realizes, so heuristic passes can match on it without parsing `instruction`
     codegen: Rc<dyn Fn(&mut CodegenContext) -> PyResult<()>>,
     profile: Rc<dyn Fn(&mut ProfileContext) -> (Profile, Profile)>
 }
 
+// mirrors `RInstruction`, but for the distributed triples rather than the reference graph nodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HoareTripleKind {
+    Computation(OpId),
+    Communication,
+    Placeholder(PlaceholderId),
+    GetAttr(ParameterId),
+    Other,
+}
+
 impl Display for HoareTriple {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{{")?;
@@ -217,45 +270,10 @@ pub enum TensorRelation {
 }
 
 impl HoareTriple {
-    fn get_cost(&self, profiler: &Profiler, sharding_ratio: &[f64]) -> f64 {
-        const epsilon: f64 = 1e-6;
-
-        // self.instructions.iter().map(|inst| match *inst {
-        //     DInstruction::Op(op_id) => {
-        //         3. * profiler.get_computation_time(&self, &profiler.module_info[op_id], sharding_ratio)
-        //     },
-        //     DInstruction::GetAttr(_, sharding_form) => {
-        //         match sharding_form {
-        //             ShardingForm::Sharded(_) => epsilon,
-        //             ShardingForm::Unsharded => {
-        //                 // additional all-reduce for gradient
-        //                 let size = if let Property::HasTensor(tensor_id, _) = self.post_conditions[0] { // TODO: how to properly get the shape information?
-        //                     profiler.rgraph[tensor_id].size() as f64
-        //                 } else {
-        //                     unreachable!()
-        //                 };
-
-        //                 profiler.get_collective_time(size, Collective::AllReduce)
-        //             },
-        //         }
-        //     },
-        //     DInstruction::Placeholder(_, _) => epsilon,
-        //     DInstruction::Output => epsilon,
-        //     DInstruction::Communication(c) => {
-        //         let original_size = if let Property::HasTensor(tensor_id, _) = self.pre_conditions.last().cloned().unwrap() { // TODO: how to properly get the shape information?
-        //             profiler.rgraph[tensor_id].size() as f64
-        //         } else {
-        //             unreachable!()
-        //         };
-
-        //         let maximum_size = original_size * sharding_ratio.iter().cloned().map(FloatOrd).max().unwrap().0;
-
-        //         profiler.get_collective_time(maximum_size, c) + profiler.get_collective_time(maximum_size, c.conjugate())
-        //     },
-        //     DInstruction::DynamicSlice(_) => epsilon,
-        // }).sum()
-
-        epsilon
+    fn get_cost(&self, profiler: &Profiler, sharding_ratios: &[f64]) -> f64 {
+        let mut ctx = ProfileContext { profiler, sharding_ratios };
+        let (forward_profile, backward_profile) = (self.profile)(&mut ctx);
+        profiler.get_time(&forward_profile, sharding_ratios) + profiler.get_time(&backward_profile, sharding_ratios)
     }
 }
 
@@ -274,10 +292,10 @@ impl<'r, 'm, 'c> Profiler<'r, 'm, 'c> {
 
         let maximum_ratio = sharding_ratios.iter().cloned().map(FloatOrd).max().unwrap().0;
         let communication_time =
-            profile.all_gather * maximum_ratio / self.cluster_info.all_gather_bandwidth +
-            profile.all_reduce * maximum_ratio / self.cluster_info.all_reduce_bandwidth +
-            profile.all_to_all * maximum_ratio / self.cluster_info.all_to_all_bandwidth +
-            profile.reduce_scatter * maximum_ratio / self.cluster_info.reduce_scatter_bandwidth;
+            self.cluster_info.collective_cost(Collective::AllGather, profile.all_gather * maximum_ratio) +
+            self.cluster_info.collective_cost(Collective::AllReduce, profile.all_reduce * maximum_ratio) +
+            self.cluster_info.collective_cost(Collective::AllToAll, profile.all_to_all * maximum_ratio) +
+            self.cluster_info.collective_cost(Collective::ReduceScatter, profile.reduce_scatter * maximum_ratio);
 
         computation_time + communication_time
     }
@@ -300,6 +318,15 @@ struct ProfileContext<'p, 's, 'r, 'm, 'c> {
 }
 
 impl<'p, 's, 'r, 'm, 'c> ProfileContext<'p, 's, 'r, 'm, 'c> {
+    fn get_bytes_by_property(&self, property: Property) -> f64 {
+        if let Property::HasTensor(tensor_id, _) = property {
+            let elements = self.get_shape_by_property(property).iter().product::<usize>() as f64;
+            elements * self.profiler.rgraph[tensor_id].dtype.size_of() as f64
+        } else {
+            unreachable!()
+        }
+    }
+
     fn get_shape_by_property(&self, property: Property) -> Shape {
         if let Property::HasTensor(tensor_id, rel) = property {
             let raw_shape = &self.profiler.rgraph[tensor_id].shape;
@@ -308,7 +335,9 @@ impl<'p, 's, 'r, 'm, 'c> ProfileContext<'p, 's, 'r, 'm, 'c> {
                 TensorRelation::Gather(dim) => {
                     let dim = dim as usize;
                     let mut shape = raw_shape.clone();
-                    shape[dim] = sharding_round(shape[dim], self.sharding_ratios)[dim];
+                    // the op's time is bounded by whichever device holds the largest shard,
+                    // not by an even full_length / n_devices split
+                    shape[dim] = sharding_round(shape[dim], self.sharding_ratios).into_iter().max().unwrap();
                     shape
                 }
             }
@@ -345,7 +374,7 @@ impl Program {
 
         remove_irrelavent_properties(&mut properties, &triple_set);
 
-        let cost = self.cost + triple.get_cost(profiler, &[0.25; 4]);
+        let cost = self.cost + triple.get_cost(profiler, &profiler.cluster_info.default_sharding_ratios());
         let ecost = 0.0;
 
         Program { triple_ids: triples, properties, cost, ecost }
@@ -619,6 +648,7 @@ pub struct RTensor {
     consumers: SVec<RNodeId>,
 
     shape: Shape,
+    dtype: DatumType,
     communicatable: bool, // hints automatically generated for certain operatios (outputs of adaptive nodes are not communicatble), can be override by user annotation
 }
 
@@ -786,6 +816,7 @@ fn initialize_parsing_handlers(py: Python) -> PyResult<BTreeMap<*mut (), &'stati
             producer: node_id,
             consumers: smallvec![],
             shape: output_shape.clone().into(),
+            dtype: input_input_tensor.dtype,
             communicatable: true
         });
 
@@ -840,6 +871,7 @@ fn initialize_parsing_handlers(py: Python) -> PyResult<BTreeMap<*mut (), &'stati
             producer: node_id,
             consumers: smallvec![],
             shape: input_input_tensor.shape.clone(),
+            dtype: input_input_tensor.dtype,
             communicatable: false
         });
 
@@ -888,6 +920,7 @@ fn initialize_parsing_handlers(py: Python) -> PyResult<BTreeMap<*mut (), &'stati
             producer: node_id,
             consumers: smallvec![],
             shape: smallvec![],
+            dtype: input_input_tensor.dtype,
             communicatable: false
         });
 
@@ -915,6 +948,211 @@ fn initialize_parsing_handlers(py: Python) -> PyResult<BTreeMap<*mut (), &'stati
         Ok(())
     }
 
+    parsing_handlers.insert(py.eval("torch.nn.functional.softmax", None, None)?.as_ptr() as _, &handle_softmax);
+    // `torch.softmax` is a distinct callable from `torch.nn.functional.softmax`, but both trace
+    // to the same op: alias it to the same handler, which canonicalizes to a single
+    // "torch.nn.functional.softmax" `Op` either way, so `analyze_rgraph`'s `for_each_op!` only
+    // ever has to match one name
+    parsing_handlers.insert(py.eval("torch.softmax", None, None)?.as_ptr() as _, &handle_softmax);
+    fn handle_softmax(ctx: ParserContext, py_node: PyObject) -> PyResult<()> {
+        assert!(py_node.getattr(ctx.py, "kwargs")?.get_item(ctx.py, "dtype").map(|x| x.is_none(ctx.py)).unwrap_or(true));
+
+        let py_id: usize = py_node.getattr(ctx.py, "meta")?.get_item(ctx.py, "id")?.extract(ctx.py)?;
+
+        let py_input_input_node = py_node.getattr(ctx.py, "kwargs")?.get_item(ctx.py, "input")?;
+        let py_input_input_id = py_input_input_node.getattr(ctx.py, "meta")?.get_item(ctx.py, "id")?.extract::<usize>(ctx.py)?;
+        let input_input_tensor_id = ctx.results[py_input_input_id].as_ref().unwrap().as_tensor();
+        let input_input_tensor = &ctx.graph[input_input_tensor_id];
+
+        // restricted to normalizing over the last dim, so `analyze_rgraph` can assume the
+        // reduction axis without having to thread a per-node dim parameter through `Op`
+        let dim: i64 = py_node.getattr(ctx.py, "kwargs")?.get_item(ctx.py, "dim")?.extract(ctx.py)?;
+        let dim = if dim < 0 { dim + input_input_tensor.n_dims() as i64 } else { dim } as Dimension;
+        if dim != input_input_tensor.n_dims() - 1 {
+            return Err(PyErr::new::<exc::ValueError, _>(ctx.py, "only softmax over the last dim is supported"));
+        }
+
+        let node_id = RNodeId(ctx.graph.nodes.len());
+        let tensor_id = RTensorId(ctx.graph.tensors.len());
+        let op_id = OpId(ctx.module_info.ops.len());
+
+        ctx.graph.tensors.push(RTensor {
+            producer: node_id,
+            consumers: smallvec![],
+            shape: input_input_tensor.shape.clone(),
+            dtype: input_input_tensor.dtype,
+            communicatable: true
+        });
+
+        ctx.graph.nodes.push(RNode {
+            inputs: smallvec![input_input_tensor_id],
+            outputs: smallvec![tensor_id],
+            instruction: RInstruction::Op(op_id)
+        });
+
+        ctx.module_info.ops.push(Op {
+            py_name: "torch.nn.functional.softmax".to_string(),
+            codegen: Rc::new(move |py, graph, inputs| {
+                let input = &inputs[0];
+                let output = graph.call_method(py, "call_function", (py.eval("torch.nn.functional.softmax", None, None)?, (input, dim)), None)?;
+                Ok(smallvec![output])
+            }),
+            flops: Rc::new(|shapes| {
+                let input_shape = &shapes[0];
+                5. * input_shape.iter().product::<usize>() as f64
+            })
+        });
+
+        ctx.graph[input_input_tensor_id].consumers.push(node_id);
+        ctx.results[py_id] = Some(EvalResult::Tensor(tensor_id));
+        Ok(())
+    }
+
+    parsing_handlers.insert(py.eval("torch.nn.functional.layer_norm", None, None)?.as_ptr() as _, &handle_layer_norm);
+    fn handle_layer_norm(ctx: ParserContext, py_node: PyObject) -> PyResult<()> {
+        let py_id: usize = py_node.getattr(ctx.py, "meta")?.get_item(ctx.py, "id")?.extract(ctx.py)?;
+
+        let py_input_input_node = py_node.getattr(ctx.py, "kwargs")?.get_item(ctx.py, "input")?;
+        let py_input_weight_node = py_node.getattr(ctx.py, "kwargs")?.get_item(ctx.py, "weight")?;
+        let py_input_bias_node = py_node.getattr(ctx.py, "kwargs")?.get_item(ctx.py, "bias")?;
+
+        let py_input_input_id = py_input_input_node.getattr(ctx.py, "meta")?.get_item(ctx.py, "id")?.extract::<usize>(ctx.py)?;
+        let py_input_weight_id = py_input_weight_node.getattr(ctx.py, "meta")?.get_item(ctx.py, "id")?.extract::<usize>(ctx.py)?;
+        let py_input_bias_id = py_input_bias_node.getattr(ctx.py, "meta")?.get_item(ctx.py, "id")?.extract::<usize>(ctx.py)?;
+
+        let input_input_tensor_id = ctx.results[py_input_input_id].as_ref().unwrap().as_tensor();
+        let input_weight_tensor_id = ctx.results[py_input_weight_id].as_ref().unwrap().as_tensor();
+        let input_bias_tensor_id = ctx.results[py_input_bias_id].as_ref().unwrap().as_tensor();
+
+        let input_input_tensor = &ctx.graph[input_input_tensor_id];
+        let input_weight_tensor = &ctx.graph[input_weight_tensor_id];
+        let input_bias_tensor = &ctx.graph[input_bias_tensor_id];
+
+        let normalized_shape: Vec<usize> = py_node.getattr(ctx.py, "kwargs")?.get_item(ctx.py, "normalized_shape")?.extract(ctx.py)?;
+        let eps: f64 = py_node.getattr(ctx.py, "kwargs")?.get_item(ctx.py, "eps")?.extract(ctx.py)?;
+
+        // restricted to normalizing over only the last dim, so `analyze_rgraph` can assume the
+        // reduction axis without having to thread a per-node dim parameter through `Op`
+        if normalized_shape.len() != 1
+            || input_input_tensor.shape.len() < normalized_shape.len()
+            || input_input_tensor.shape[input_input_tensor.shape.len() - normalized_shape.len()..] != normalized_shape[..]
+            || input_weight_tensor.shape[..] != normalized_shape[..]
+            || input_bias_tensor.shape[..] != normalized_shape[..]
+        {
+            return Err(PyErr::new::<exc::ValueError, _>(ctx.py, "only layer_norm over the last dim is supported"));
+        }
+
+        let node_id = RNodeId(ctx.graph.nodes.len());
+        let tensor_id = RTensorId(ctx.graph.tensors.len());
+        let op_id = OpId(ctx.module_info.ops.len());
+
+        ctx.graph.tensors.push(RTensor {
+            producer: node_id,
+            consumers: smallvec![],
+            shape: input_input_tensor.shape.clone(),
+            dtype: input_input_tensor.dtype,
+            communicatable: true
+        });
+
+        ctx.graph.nodes.push(RNode {
+            inputs: smallvec![input_input_tensor_id, input_weight_tensor_id, input_bias_tensor_id],
+            outputs: smallvec![tensor_id],
+            instruction: RInstruction::Op(op_id)
+        });
+
+        ctx.module_info.ops.push(Op {
+            py_name: "torch.nn.functional.layer_norm".to_string(),
+            codegen: Rc::new(move |py, graph, inputs| {
+                if let [input, weight, bias] = inputs {
+                    let kwargs = py_dict!(py, input => input, normalized_shape => normalized_shape.clone(), weight => weight, bias => bias, eps => eps);
+                    let output = graph.call_method(py, "call_function", (py.eval("torch.nn.functional.layer_norm", None, None)?, PyTuple::empty(py), kwargs), None)?;
+                    Ok(smallvec![output])
+                } else {
+                    unreachable!()
+                }
+            }),
+            flops: Rc::new(|shapes| {
+                let input_shape = &shapes[0];
+                5. * input_shape.iter().product::<usize>() as f64
+            })
+        });
+
+        ctx.graph[input_input_tensor_id].consumers.push(node_id);
+        ctx.graph[input_weight_tensor_id].consumers.push(node_id);
+        ctx.graph[input_bias_tensor_id].consumers.push(node_id);
+
+        ctx.results[py_id] = Some(EvalResult::Tensor(tensor_id));
+        Ok(())
+    }
+
+    parsing_handlers.insert(py.eval("torch.nn.functional.embedding", None, None)?.as_ptr() as _, &handle_embedding);
+    fn handle_embedding(ctx: ParserContext, py_node: PyObject) -> PyResult<()> {
+        assert!(py_node.getattr(ctx.py, "kwargs")?.get_item(ctx.py, "padding_idx").map(|x| x.is_none(ctx.py)).unwrap_or(true));
+        assert!(py_node.getattr(ctx.py, "kwargs")?.get_item(ctx.py, "max_norm").map(|x| x.is_none(ctx.py)).unwrap_or(true));
+
+        let py_id: usize = py_node.getattr(ctx.py, "meta")?.get_item(ctx.py, "id")?.extract(ctx.py)?;
+
+        let py_input_input_node = py_node.getattr(ctx.py, "kwargs")?.get_item(ctx.py, "input")?;
+        let py_input_weight_node = py_node.getattr(ctx.py, "kwargs")?.get_item(ctx.py, "weight")?;
+
+        let py_input_input_id = py_input_input_node.getattr(ctx.py, "meta")?.get_item(ctx.py, "id")?.extract::<usize>(ctx.py)?;
+        let py_input_weight_id = py_input_weight_node.getattr(ctx.py, "meta")?.get_item(ctx.py, "id")?.extract::<usize>(ctx.py)?;
+
+        let input_input_tensor_id = ctx.results[py_input_input_id].as_ref().unwrap().as_tensor();
+        let input_weight_tensor_id = ctx.results[py_input_weight_id].as_ref().unwrap().as_tensor();
+
+        let input_input_tensor = &ctx.graph[input_input_tensor_id];
+        let input_weight_tensor = &ctx.graph[input_weight_tensor_id];
+
+        let output_shape = match &input_weight_tensor.shape[..] {
+            [_vocab_size, features] => [&input_input_tensor.shape[..], &[*features]].concat(),
+            _ => panic!("invalid embedding weight shape")
+        };
+
+        let node_id = RNodeId(ctx.graph.nodes.len());
+        let tensor_id = RTensorId(ctx.graph.tensors.len());
+        let op_id = OpId(ctx.module_info.ops.len());
+
+        ctx.graph.tensors.push(RTensor {
+            producer: node_id,
+            consumers: smallvec![],
+            shape: output_shape.into(),
+            dtype: input_weight_tensor.dtype,
+            communicatable: true
+        });
+
+        ctx.graph.nodes.push(RNode {
+            inputs: smallvec![input_input_tensor_id, input_weight_tensor_id],
+            outputs: smallvec![tensor_id],
+            instruction: RInstruction::Op(op_id)
+        });
+
+        ctx.module_info.ops.push(Op {
+            py_name: "torch.nn.functional.embedding".to_string(),
+            codegen: Rc::new(|py, graph, inputs| {
+                if let [input, weight] = inputs {
+                    let output = graph.call_method(py, "call_function", (py.eval("torch.nn.functional.embedding", None, None)?, (input, weight)), None)?;
+                    Ok(smallvec![output])
+                } else {
+                    unreachable!()
+                }
+            }),
+            flops: Rc::new(|shapes| {
+                if let [_input_shape, weight_shape] = shapes {
+                    weight_shape.iter().product::<usize>() as f64
+                } else {
+                    unreachable!()
+                }
+            })
+        });
+
+        ctx.graph[input_input_tensor_id].consumers.push(node_id);
+        ctx.graph[input_weight_tensor_id].consumers.push(node_id);
+
+        ctx.results[py_id] = Some(EvalResult::Tensor(tensor_id));
+        Ok(())
+    }
+
     Ok(parsing_handlers)
 }
 
@@ -928,6 +1166,11 @@ macro_rules! py_dict {
     }}
 }
 
+fn extract_dtype_from_meta(py: Python, py_node: &PyObject) -> PyResult<DatumType> {
+    let py_dtype = py_node.getattr(py, "meta")?.get_item(py, "tensor_meta")?.getattr(py, "dtype")?;
+    DatumType::from_py(py, &py_dtype)
+}
+
 fn load_fx_graph(py: Python, py_graph_module: PyObject, py_input_shape_dict: PyObject) -> PyResult<(RGraph, ModuleInfo)> {
     let mut graph = RGraph::default();
     let mut module_info = ModuleInfo::default();
@@ -952,6 +1195,7 @@ fn load_fx_graph(py: Python, py_graph_module: PyObject, py_input_shape_dict: PyO
                 let placeholder_id = PlaceholderId(module_info.placeholders.len());
                 let name: String = py_node.getattr(py, "target")?.extract(py)?;
                 let shape: Vec<usize> = py_input_shape_dict.get_item(py, &name)?.extract(py)?;
+                let dtype = extract_dtype_from_meta(py, &py_node)?;
 
                 module_info.placeholders.push(name);
 
@@ -968,6 +1212,7 @@ fn load_fx_graph(py: Python, py_graph_module: PyObject, py_input_shape_dict: PyO
                     producer: node_id,
                     consumers: smallvec![],
                     shape: shape.into(),
+                    dtype,
                     communicatable: false
                 });
 
@@ -983,6 +1228,7 @@ fn load_fx_graph(py: Python, py_graph_module: PyObject, py_input_shape_dict: PyO
                     "get_shape_of_param_or_buffer(graph_module, node)",
                     None, Some(&py_dict!(py, graph_module => py_graph_module, node => py_node))
                 )?.extract(py)?;
+                let dtype = extract_dtype_from_meta(py, &py_node)?;
 
                 let node_id = RNodeId(graph.nodes.len());
                 let tensor_id = RTensorId(graph.tensors.len());
@@ -997,6 +1243,7 @@ fn load_fx_graph(py: Python, py_graph_module: PyObject, py_input_shape_dict: PyO
                     producer: node_id,
                     consumers: smallvec![],
                     shape: shape.into(),
+                    dtype,
                     communicatable: false
                 });
 
@@ -1056,19 +1303,69 @@ fn load_fx_graph(py: Python, py_graph_module: PyObject, py_input_shape_dict: PyO
     Ok((graph, module_info))
 }
 
-fn analyze_rgraph(rgraph: &RGraph, module_info: &ModuleInfo) -> Vec<HoareTriple> {
-    let mut triples = vec![];
+fn add_triple(
+    triples: &mut Vec<HoareTriple>,
+    pre_conditions: SVec<Property, 4>,
+    post_conditions: SVec<Property>,
+    instruction: String,
+    kind: HoareTripleKind,
+    codegen: Rc<dyn Fn(&mut CodegenContext) -> PyResult<()>>,
+    profile: Rc<dyn Fn(&mut ProfileContext) -> (Profile, Profile)>
+) {
+    triples.push(HoareTriple {
+        pre_conditions,
+        post_conditions,
+        negative_post_conditions: vec![],
+        instruction,
+        kind,
+        codegen,
+        profile
+    });
+}
 
-    let mut add_triple = |pre_conditions, post_conditions, instruction, codegen, profile| {
-        triples.push(HoareTriple {
-            pre_conditions,
-            post_conditions,
-            negative_post_conditions: vec![],
-            instruction,
-            codegen,
-            profile
-        });
-    };
+// an op triple is just a `HoareTriple` whose codegen/profile are derived from the op's own
+// codegen/flops function; this takes `module_info` explicitly (rather than closing over it)
+// so callers can still mutate `module_info` (to synthesize new ops) in between calls
+fn add_comp_triple(
+    triples: &mut Vec<HoareTriple>,
+    module_info: &ModuleInfo,
+    pre_conditions: SVec<Property, 4>,
+    post_conditions: SVec<Property>,
+    op_id: OpId
+) {
+    let op_codegen_fun = module_info[op_id].codegen.clone();
+    let op_flops_fun = module_info[op_id].flops.clone();
+
+    add_triple(
+        triples,
+        pre_conditions.clone(),
+        post_conditions.clone(),
+        module_info[op_id].py_name.clone(),
+        HoareTripleKind::Computation(op_id),
+        Rc::new({
+            let pre_conditions = pre_conditions.clone();
+            let post_conditions = post_conditions.clone();
+            move |ctx| {
+                let inputs: Vec<_> = pre_conditions.iter().map(|p| ctx.get_property_implementation(*p)).collect();
+                let outputs = op_codegen_fun(ctx.py, &ctx.graph, &inputs)?;
+                for (output_property, py_output) in post_conditions.iter().zip(outputs) {
+                    ctx.set_property_implementation(*output_property, py_output);
+                }
+                Ok(())
+            }
+        }),
+        Rc::new(move |ctx| {
+            let shapes: Vec<_> = pre_conditions.iter().map(|p| ctx.get_shape_by_property(*p)).collect();
+            let flops = op_flops_fun(&shapes);
+            let forward_profile = Profile { flops, ..Default::default() };
+            let backward_profile = Profile { flops: 2. * flops, ..Default::default() };
+            (forward_profile, backward_profile)
+        })
+    )
+}
+
+fn analyze_rgraph(rgraph: &mut RGraph, module_info: &mut ModuleInfo) -> Vec<HoareTriple> {
+    let mut triples = vec![];
 
     // basics: Placeholder, GetAttr, Output, and identity for ops
     for (node_id, node) in rgraph.nodes.iter().enumerate() {
@@ -1081,9 +1378,11 @@ fn analyze_rgraph(rgraph: &RGraph, module_info: &ModuleInfo) -> Vec<HoareTriple>
                 let placeholder_name = &module_info.placeholders[placeholder_id.0];
 
                 add_triple(
+                    &mut triples,
                     smallvec![],
                     smallvec![Property::identity(tensor_id)],
                     format!("placeholder_unsharded(\"{placeholder_name}\")"),
+                    HoareTripleKind::Placeholder(placeholder_id),
                     Rc::new({
                         let placeholder_name = placeholder_name.clone();
                         move |ctx| {
@@ -1099,9 +1398,11 @@ fn analyze_rgraph(rgraph: &RGraph, module_info: &ModuleInfo) -> Vec<HoareTriple>
                     let dim = dim as Dimension;
 
                     add_triple(
+                        &mut triples,
                         smallvec![],
                         smallvec![Property::gather(tensor_id, dim)],
                         format!("placeholder_shard(\"{placeholder_name}\", dim={dim}])"),
+                        HoareTripleKind::Placeholder(placeholder_id),
                         Rc::new({
                             let placeholder_name = placeholder_name.clone();
                             move |ctx| {
@@ -1124,9 +1425,11 @@ fn analyze_rgraph(rgraph: &RGraph, module_info: &ModuleInfo) -> Vec<HoareTriple>
                 let parameter_name = &module_info.parameters[parameter_id.0];
 
                 add_triple(
+                    &mut triples,
                     smallvec![],
                     smallvec![Property::identity(tensor_id)],
                     format!("get_attr_unsharded(\"{parameter_name}\")"),
+                    HoareTripleKind::GetAttr(parameter_id),
                     Rc::new({
                         let parameter_name = parameter_name.clone();
                         move |ctx| {
@@ -1138,9 +1441,9 @@ fn analyze_rgraph(rgraph: &RGraph, module_info: &ModuleInfo) -> Vec<HoareTriple>
                     Rc::new({
                         let parameter_name = parameter_name.clone();
                         move |ctx| {
-                            let size = ctx.get_shape_by_property(Property::identity(tensor_id)).iter().product::<usize>();
+                            let bytes = ctx.get_bytes_by_property(Property::identity(tensor_id));
                             let forward_profile = Default::default();
-                            let backward_profile = Profile { all_reduce: size as f64, ..Default::default() };
+                            let backward_profile = Profile { all_reduce: bytes, ..Default::default() };
                             (forward_profile, backward_profile)
                         }
                     })
@@ -1150,9 +1453,11 @@ fn analyze_rgraph(rgraph: &RGraph, module_info: &ModuleInfo) -> Vec<HoareTriple>
                     let dim = dim as Dimension;
 
                     add_triple(
+                        &mut triples,
                         smallvec![],
                         smallvec![Property::gather(tensor_id, dim)],
                         format!("get_attr_shard(\"{parameter_name}\", dim={dim}])"),
+                        HoareTripleKind::GetAttr(parameter_id),
                         Rc::new(|ctx| {
                             todo!() // we need to actually shard the model here
                         }),
@@ -1165,9 +1470,11 @@ fn analyze_rgraph(rgraph: &RGraph, module_info: &ModuleInfo) -> Vec<HoareTriple>
                 let tensor_id = node.inputs[0];
 
                 add_triple(
+                    &mut triples,
                     smallvec![Property::reduce(tensor_id)],
                     smallvec![Property::Finished],
                     format!("output"),
+                    HoareTripleKind::Other,
                     Rc::new(move |ctx| {
                         let py_input = ctx.get_property_implementation(Property::reduce(tensor_id));
                         ctx.fx_output(py_input)?;
@@ -1191,49 +1498,57 @@ fn analyze_rgraph(rgraph: &RGraph, module_info: &ModuleInfo) -> Vec<HoareTriple>
 
         for dim in 0..tensor.n_dims() {
             add_triple(
+                &mut triples,
                 smallvec![Property::gather(tensor_id, dim)],
                 smallvec![Property::identity(tensor_id)],
                 format!("all_gather(dim={dim})"),
+                HoareTripleKind::Communication,
                 Rc::new(move |ctx| { todo!() }),
                 Rc::new(move |ctx| {
-                    let size = ctx.get_shape_by_property(Property::identity(tensor_id)).iter().product::<usize>();
-                    let forward_profile = Profile { all_gather: size as f64, ..Default::default() };
-                    let backward_profile = Profile { reduce_scatter: size as f64, ..Default::default() };
+                    let bytes = ctx.get_bytes_by_property(Property::identity(tensor_id));
+                    let forward_profile = Profile { all_gather: bytes, ..Default::default() };
+                    let backward_profile = Profile { reduce_scatter: bytes, ..Default::default() };
                     (forward_profile, backward_profile)
                 })
             );
 
             add_triple(
+                &mut triples,
                 smallvec![Property::identity(tensor_id)],
                 smallvec![Property::gather(tensor_id, dim)],
                 format!("dynamic_slice(dim={dim})"),
+                HoareTripleKind::Other,
                 Rc::new(move |ctx| { todo!() }),
                 Rc::new(move |ctx| { Default::default() })
             );
 
             add_triple(
+                &mut triples,
                 smallvec![Property::reduce(tensor_id)],
                 smallvec![Property::gather(tensor_id, dim)],
                 format!("reduce_scatter(dim={dim})"),
+                HoareTripleKind::Communication,
                 Rc::new(move |ctx| { todo!() }),
                 Rc::new(move |ctx| {
-                    let size = ctx.get_shape_by_property(Property::identity(tensor_id)).iter().product::<usize>();
-                    let forward_profile = Profile { reduce_scatter: size as f64, ..Default::default() };
-                    let backward_profile = Profile { all_gather: size as f64, ..Default::default() };
+                    let bytes = ctx.get_bytes_by_property(Property::identity(tensor_id));
+                    let forward_profile = Profile { reduce_scatter: bytes, ..Default::default() };
+                    let backward_profile = Profile { all_gather: bytes, ..Default::default() };
                     (forward_profile, backward_profile)
                 })
             );
         }
 
         add_triple(
+            &mut triples,
             smallvec![Property::reduce(tensor_id)],
             smallvec![Property::identity(tensor_id)],
             format!("all_reduce"),
+            HoareTripleKind::Communication,
             Rc::new(move |ctx| { todo!() }),
             Rc::new(move |ctx| {
-                let size = ctx.get_shape_by_property(Property::identity(tensor_id)).iter().product::<usize>();
-                let forward_profile = Profile { all_reduce: size as f64, ..Default::default() };
-                let backward_profile = Profile { all_reduce: size as f64, ..Default::default() };
+                let bytes = ctx.get_bytes_by_property(Property::identity(tensor_id));
+                let forward_profile = Profile { all_reduce: bytes, ..Default::default() };
+                let backward_profile = Profile { all_reduce: bytes, ..Default::default() };
                 (forward_profile, backward_profile)
             })
         );
@@ -1242,14 +1557,16 @@ fn analyze_rgraph(rgraph: &RGraph, module_info: &ModuleInfo) -> Vec<HoareTriple>
             for j in 0..tensor.n_dims() {
                 if i != j {
                     add_triple(
+                        &mut triples,
                         smallvec![Property::gather(tensor_id, i)],
                         smallvec![Property::gather(tensor_id, j)],
                         format!("all_to_all(cat={i}, split={j})"),
+                        HoareTripleKind::Communication,
                         Rc::new(move |ctx| { todo!() }),
                         Rc::new(move |ctx| {
-                            let size = ctx.get_shape_by_property(Property::identity(tensor_id)).iter().product::<usize>();
-                            let forward_profile = Profile { all_to_all: size as f64, ..Default::default() };
-                            let backward_profile = Profile { all_to_all: size as f64, ..Default::default() };
+                            let bytes = ctx.get_bytes_by_property(Property::identity(tensor_id));
+                            let forward_profile = Profile { all_to_all: bytes, ..Default::default() };
+                            let backward_profile = Profile { all_to_all: bytes, ..Default::default() };
                             (forward_profile, backward_profile)
                         })
                     );
@@ -1269,39 +1586,23 @@ fn analyze_rgraph(rgraph: &RGraph, module_info: &ModuleInfo) -> Vec<HoareTriple>
         }}
     }
 
-    let mut add_comp_triple = |pre_conditions: SVec<Property, 4>, post_conditions: SVec<Property>, op_id: OpId| {
-        let op_codegen_fun = module_info[op_id].codegen.clone();
-        let op_flops_fun = module_info[op_id].flops.clone();
-
-        add_triple(
-            pre_conditions.clone(),
-            post_conditions.clone(),
-            module_info[op_id].py_name.clone(),
-            Rc::new({
-                let pre_conditions = pre_conditions.clone();
-                let post_conditions = post_conditions.clone();
-                move |ctx| {
-                    let inputs: Vec<_> = pre_conditions.iter().map(|p| ctx.get_property_implementation(*p)).collect();
-                    let outputs = op_codegen_fun(ctx.py, &ctx.graph, &inputs)?;
-                    for (output_property, py_output) in post_conditions.iter().zip(outputs) {
-                        ctx.set_property_implementation(*output_property, py_output);
-                    }
-                    Ok(())
-                }
-            }),
-            Rc::new(move |ctx| {
-                let shapes: Vec<_> = pre_conditions.iter().map(|p| ctx.get_shape_by_property(*p)).collect();
-                let flops = op_flops_fun(&shapes);
-                let forward_profile = Profile { flops, ..Default::default() };
-                let backward_profile = Profile { flops: 2. * flops, ..Default::default() };
-                (forward_profile, backward_profile)
-            })
-        )
-    };
-
     // Linear
+    // row-parallel (reduction) strategy needs to synthesize a bias-free matmul, a collective,
+    // and a bias-add, which would mutate `rgraph`/`module_info` while `for_each_op!` is still
+    // borrowing them for iteration; so we just record what to synthesize here and do it below,
+    // once the loop (and its borrow) is done.
+    struct PendingRowParallelLinear {
+        input_tensor_id: RTensorId,
+        weight_tensor_id: RTensorId,
+        bias_tensor_id: RTensorId,
+        output_tensor_id: RTensorId,
+    }
+    let mut pending_row_parallel_linear = vec![];
+
     for_each_op!("torch.nn.functional.linear", |node_id, node, op_id| {
         add_comp_triple(
+            &mut triples,
+            module_info,
             node.inputs.iter().cloned().map(Property::identity).collect(),
             node.outputs.iter().cloned().map(Property::identity).collect(),
             op_id,
@@ -1310,6 +1611,8 @@ fn analyze_rgraph(rgraph: &RGraph, module_info: &ModuleInfo) -> Vec<HoareTriple>
         // data parallelism
         for dim in 0..rgraph[node.inputs[0]].n_dims() - 1 {
             add_comp_triple(
+                &mut triples,
+                module_info,
                 smallvec![
                     Property::gather(node.inputs[0], dim),
                     Property::identity(node.inputs[1]),
@@ -1322,6 +1625,8 @@ fn analyze_rgraph(rgraph: &RGraph, module_info: &ModuleInfo) -> Vec<HoareTriple>
 
         // feature partition
         add_comp_triple(
+            &mut triples,
+            module_info,
             smallvec![
                 Property::identity(node.inputs[0]),
                 Property::gather(node.inputs[1], 0),
@@ -1331,14 +1636,121 @@ fn analyze_rgraph(rgraph: &RGraph, module_info: &ModuleInfo) -> Vec<HoareTriple>
             op_id,
         );
 
-        // reduction?
-        // this requires arithemetic replacement (change to matmul + allreduce + add)
-        // we also hit Rust aliasing rule here as the loop already borrows the graph
+        // reduction: input gathered on its contraction dim, weight gathered on dim 1 (also
+        // contraction); the matmul is only a partial sum on each device
+        pending_row_parallel_linear.push(PendingRowParallelLinear {
+            input_tensor_id: node.inputs[0],
+            weight_tensor_id: node.inputs[1],
+            bias_tensor_id: node.inputs[2],
+            output_tensor_id: node.outputs[0],
+        });
     });
 
+    for pending in pending_row_parallel_linear {
+        let PendingRowParallelLinear { input_tensor_id, weight_tensor_id, bias_tensor_id, output_tensor_id } = pending;
+
+        // a bias-free matmul producing a partial sum on each device
+        let partial_node_id = RNodeId(rgraph.nodes.len());
+        let partial_tensor_id = RTensorId(rgraph.tensors.len());
+        let matmul_op_id = OpId(module_info.ops.len());
+        let output_tensor = &rgraph[output_tensor_id];
+        let partial_shape = output_tensor.shape.clone();
+        let partial_dtype = output_tensor.dtype;
+
+        rgraph.tensors.push(RTensor {
+            producer: partial_node_id,
+            consumers: smallvec![],
+            shape: partial_shape,
+            dtype: partial_dtype,
+            communicatable: true
+        });
+
+        rgraph.nodes.push(RNode {
+            inputs: smallvec![input_tensor_id, weight_tensor_id],
+            outputs: smallvec![partial_tensor_id],
+            instruction: RInstruction::Op(matmul_op_id)
+        });
+
+        module_info.ops.push(Op {
+            py_name: "torch.nn.functional.linear".to_string(),
+            codegen: Rc::new(|py, graph, inputs| {
+                if let [input, weight] = inputs {
+                    let output = graph.call_method(py, "call_function", (py.eval("torch.nn.functional.linear", None, None)?, (input, weight)), None)?;
+                    Ok(smallvec![output])
+                } else {
+                    unreachable!()
+                }
+            }),
+            flops: Rc::new(|shapes| {
+                if let [input_shape, weight_shape] = shapes {
+                    3. * input_shape.iter().product::<usize>() as f64 * weight_shape[0] as f64
+                } else {
+                    unreachable!()
+                }
+            })
+        });
+
+        rgraph[input_tensor_id].consumers.push(partial_node_id);
+        rgraph[weight_tensor_id].consumers.push(partial_node_id);
+
+        add_comp_triple(
+            &mut triples,
+            module_info,
+            smallvec![
+                Property::gather(input_tensor_id, rgraph[input_tensor_id].n_dims() - 1),
+                Property::gather(weight_tensor_id, 1),
+            ],
+            smallvec![Property::reduce(partial_tensor_id)],
+            matmul_op_id,
+        );
+
+        // the collective: all-reduce the partial sums into the true matmul result
+        add_triple(
+            &mut triples,
+            smallvec![Property::reduce(partial_tensor_id)],
+            smallvec![Property::identity(partial_tensor_id)],
+            format!("all_reduce"),
+            HoareTripleKind::Communication,
+            Rc::new(move |ctx| { todo!() }),
+            Rc::new(move |ctx| {
+                let bytes = ctx.get_bytes_by_property(Property::identity(partial_tensor_id));
+                let forward_profile = Profile { all_reduce: bytes, ..Default::default() };
+                let backward_profile = Profile { all_reduce: bytes, ..Default::default() };
+                (forward_profile, backward_profile)
+            })
+        );
+
+        // the bias is only added once, after the reduce
+        let add_op_id = OpId(module_info.ops.len());
+        module_info.ops.push(Op {
+            py_name: "operator.add".to_string(),
+            codegen: Rc::new(|py, graph, inputs| {
+                if let [matmul_result, bias] = inputs {
+                    let output = graph.call_method(py, "call_function", (py.eval("operator.add", None, None)?, (matmul_result, bias)), None)?;
+                    Ok(smallvec![output])
+                } else {
+                    unreachable!()
+                }
+            }),
+            flops: Rc::new(|shapes| {
+                shapes[0].iter().product::<usize>() as f64
+            })
+        });
+
+        add_comp_triple(
+            &mut triples,
+            module_info,
+            smallvec![Property::identity(partial_tensor_id), Property::identity(bias_tensor_id)],
+            smallvec![Property::identity(output_tensor_id)],
+            add_op_id,
+        );
+    }
+
     // Sigmoid
     for_each_op!("torch.sigmoid", |node_id, node, op_id| {
         add_comp_triple(
+            &mut triples,
+            module_info,
             node.inputs.iter().cloned().map(Property::identity).collect(),
             node.outputs.iter().cloned().map(Property::identity).collect(),
             op_id,
@@ -1346,6 +1758,8 @@ fn analyze_rgraph(rgraph: &RGraph, module_info: &ModuleInfo) -> Vec<HoareTriple>
 
         for dim in 0..rgraph[node.inputs[0]].n_dims() {
             add_comp_triple(
+                &mut triples,
+                module_info,
                 smallvec![Property::gather(node.inputs[0], dim)],
                 smallvec![Property::gather(node.outputs[0], dim)],
                 op_id,
@@ -1356,6 +1770,8 @@ fn analyze_rgraph(rgraph: &RGraph, module_info: &ModuleInfo) -> Vec<HoareTriple>
     // Sum
     for_each_op!("torch.sum", |node_id, node, op_id| {
         add_comp_triple(
+            &mut triples,
+            module_info,
             node.inputs.iter().cloned().map(Property::identity).collect(),
             node.outputs.iter().cloned().map(Property::identity).collect(),
             op_id,
@@ -1363,6 +1779,8 @@ fn analyze_rgraph(rgraph: &RGraph, module_info: &ModuleInfo) -> Vec<HoareTriple>
 
         for dim in 0..rgraph[node.inputs[0]].n_dims() {
             add_comp_triple(
+                &mut triples,
+                module_info,
                 smallvec![Property::gather(node.inputs[0], dim)],
                 smallvec![Property::reduce(node.outputs[0])],
                 op_id,
@@ -1370,140 +1788,397 @@ fn analyze_rgraph(rgraph: &RGraph, module_info: &ModuleInfo) -> Vec<HoareTriple>
         }
 
         add_comp_triple(
+            &mut triples,
+            module_info,
             smallvec![Property::reduce(node.inputs[0])],
             smallvec![Property::reduce(node.outputs[0])],
             op_id,
         );
     });
 
+    // Softmax
+    for_each_op!("torch.nn.functional.softmax", |node_id, node, op_id| {
+        add_comp_triple(
+            &mut triples,
+            module_info,
+            node.inputs.iter().cloned().map(Property::identity).collect(),
+            node.outputs.iter().cloned().map(Property::identity).collect(),
+            op_id,
+        );
+
+        // every dim except the last (the normalized axis) can be sharded independently;
+        // sharding the normalized axis itself would require rewriting this into a
+        // local-stat + all-reduce of the running max/sum, which isn't implemented here
+        for dim in 0..rgraph[node.inputs[0]].n_dims() - 1 {
+            add_comp_triple(
+                &mut triples,
+                module_info,
+                smallvec![Property::gather(node.inputs[0], dim)],
+                smallvec![Property::gather(node.outputs[0], dim)],
+                op_id,
+            );
+        }
+    });
+
+    // LayerNorm
+    for_each_op!("torch.nn.functional.layer_norm", |node_id, node, op_id| {
+        add_comp_triple(
+            &mut triples,
+            module_info,
+            node.inputs.iter().cloned().map(Property::identity).collect(),
+            node.outputs.iter().cloned().map(Property::identity).collect(),
+            op_id,
+        );
+
+        // every dim except the last (the normalized axis) can be sharded independently;
+        // the weight/bias only cover the normalized axis and stay whole on every device
+        for dim in 0..rgraph[node.inputs[0]].n_dims() - 1 {
+            add_comp_triple(
+                &mut triples,
+                module_info,
+                smallvec![
+                    Property::gather(node.inputs[0], dim),
+                    Property::identity(node.inputs[1]),
+                    Property::identity(node.inputs[2]),
+                ],
+                smallvec![Property::gather(node.outputs[0], dim)],
+                op_id,
+            );
+        }
+    });
+
+    // Embedding
+    for_each_op!("torch.nn.functional.embedding", |node_id, node, op_id| {
+        add_comp_triple(
+            &mut triples,
+            module_info,
+            node.inputs.iter().cloned().map(Property::identity).collect(),
+            node.outputs.iter().cloned().map(Property::identity).collect(),
+            op_id,
+        );
+
+        // vocabulary partition: each device holds a row-slice of the embedding table and must
+        // produce zeros for out-of-range indices before the all-reduce; that needs an index
+        // offset + out-of-range mask around the plain `embedding` call, which `add_comp_triple`
+        // can't express (it always reuses the op's single shared, unsharded codegen), so codegen
+        // stays a `todo!()` like the other not-yet-implemented collectives until that's written
+        let op_flops_fun = module_info[op_id].flops.clone();
+        let input_tensor_id = node.inputs[0];
+        let weight_tensor_id = node.inputs[1];
+        add_triple(
+            &mut triples,
+            smallvec![
+                Property::identity(input_tensor_id),
+                Property::gather(weight_tensor_id, 0),
+            ],
+            smallvec![Property::reduce(node.outputs[0])],
+            format!("{} (vocab partition)", module_info[op_id].py_name),
+            HoareTripleKind::Computation(op_id),
+            Rc::new(move |ctx| { todo!() }),
+            Rc::new(move |ctx| {
+                let shapes = vec![ctx.get_shape_by_property(Property::identity(input_tensor_id)), ctx.get_shape_by_property(Property::gather(weight_tensor_id, 0))];
+                let flops = op_flops_fun(&shapes);
+                let forward_profile = Profile { flops, ..Default::default() };
+                let backward_profile = Profile { flops: 2. * flops, ..Default::default() };
+                (forward_profile, backward_profile)
+            })
+        );
+    });
+
     triples
 }
 
 mod heuristics {
     use super::*;
 
+    // a post-processing pass over the generated triples: it may tighten pre/post conditions
+    // (to encode an ordering constraint) or rewrite the triple list outright (to fuse triples
+    // together), and may seed `default_properties` with whatever it needs the search to start with
+    pub trait HeuristicPass {
+        fn apply(&self, triples: &mut Vec<HoareTriple>, default_properties: &mut Vec<Property>, rgraph: &RGraph);
+    }
+
     // each Op can only be computed once
-    // pub fn compute_only_once(triples: &mut Vec<HoareTriple>, default_properties: &mut Vec<Property>, _rgraph: &RGraph) {
-    //     for triple in triples {
-    //         if let [DInstruction::Op(op_id)] = triple.instructions[..] {
-    //             triple.pre_conditions.push(Property::AllowComputation(op_id));
-    //             triple.negative_post_conditions.push(Property::AllowComputation(op_id));
-    //             default_properties.push(Property::AllowComputation(op_id));
-    //         }
-    //     }
-    // }
+    pub struct ComputeOnlyOnce;
+
+    impl HeuristicPass for ComputeOnlyOnce {
+        fn apply(&self, triples: &mut Vec<HoareTriple>, default_properties: &mut Vec<Property>, _rgraph: &RGraph) {
+            for triple in triples.iter_mut() {
+                if let HoareTripleKind::Computation(op_id) = triple.kind {
+                    triple.pre_conditions.push(Property::AllowComputation(op_id));
+                    triple.negative_post_conditions.push(Property::AllowComputation(op_id));
+                    default_properties.push(Property::AllowComputation(op_id));
+                }
+            }
+        }
+    }
 
     // communication must happen in order
-    // pub fn ordered_communication(triples: &mut Vec<HoareTriple>, default_properties: &mut Vec<Property>, rgraph: &RGraph) {
-    //     for triple in triples {
-    //         if let [DInstruction::Communication(_)] = triple.instructions[..] {
-    //             if let Property::HasTensor(tensor_id, _) = triple.post_conditions[0] {
-    //                 triple.pre_conditions.push(Property::AllowCommunication(tensor_id));
-    //                 for i in 0..=tensor_id.0 {
-    //                     if rgraph[RTensorId(i)].communicatable {
-    //                         triple.negative_post_conditions.push(Property::AllowCommunication(RTensorId(i)));
-    //                     }
-    //                 }
-    //                 default_properties.push(Property::AllowCommunication(tensor_id));
-    //             } else {
-    //                 unreachable!();
-    //             }
-    //         }
-    //     }
-    // }
-
-    // placeholder must happen in order
-    // pub fn ordered_placeholder(triples: &mut Vec<HoareTriple>, default_properties: &mut Vec<Property>, rgraph: &RGraph) {
-    //     for triple in triples {
-    //         if let [DInstruction::Placeholder(placeholder_id, _)] = triple.instructions[..] {
-    //             triple.pre_conditions.push(Property::AllowPlaceholder(placeholder_id));
-    //             for i in 0..=placeholder_id.0 {
-    //                 triple.negative_post_conditions.push(Property::AllowPlaceholder(PlaceholderId(i)));
-    //             }
-    //             default_properties.push(Property::AllowPlaceholder(placeholder_id));
-    //         }
-    //     }
-    // }
-
-    // placeholder must happen in order, alternative implementation
-    // pub fn ordered_placeholder_chain(triples: &mut Vec<HoareTriple>, default_properties: &mut Vec<Property>, rgraph: &RGraph) {
-    //     for triple in triples {
-    //         if let [DInstruction::Placeholder(placeholder_id, _)] = triple.instructions[..] {
-    //             triple.pre_conditions.push(Property::AllowPlaceholder(placeholder_id));
-    //             triple.post_conditions.push(Property::AllowPlaceholder(placeholder_id + 1));
-    //             triple.negative_post_conditions.push(Property::AllowPlaceholder(placeholder_id));
-    //         }
-    //     }
-    //     default_properties.push(Property::AllowPlaceholder(PlaceholderId(0)))
-    // }
-
-    // get attr must happen in order
-    // pub fn ordered_get_attr(triples: &mut Vec<HoareTriple>, default_properties: &mut Vec<Property>, rgraph: &RGraph) {
-    //     for triple in triples {
-    //         if let [DInstruction::GetAttr(parameter_id, _)] = triple.instructions[..] {
-    //             triple.pre_conditions.push(Property::AllowGetAttr(parameter_id));
-    //             for i in 0..=parameter_id.0 {
-    //                 triple.negative_post_conditions.push(Property::AllowGetAttr(ParameterId(i)));
-    //             }
-    //             default_properties.push(Property::AllowGetAttr(parameter_id));
-    //         }
-    //     }
-    // }
-
-    // get attr must happen in order, alternative implementation
-    // pub fn ordered_get_attr_chain(triples: &mut Vec<HoareTriple>, default_properties: &mut Vec<Property>, rgraph: &RGraph) {
-    //     for triple in triples {
-    //         if let [DInstruction::GetAttr(parameter_id, _)] = triple.instructions[..] {
-    //             triple.pre_conditions.push(Property::AllowGetAttr(parameter_id));
-    //             triple.post_conditions.push(Property::AllowGetAttr(parameter_id + 1));
-    //             triple.negative_post_conditions.push(Property::AllowGetAttr(parameter_id));
-    //         }
-    //     }
-    //     default_properties.push(Property::AllowGetAttr(ParameterId(0)))
-    // }
-
-    // fuse communication triples into its consumer
-    // pub fn fuse_communication_forward(triples: &mut Vec<HoareTriple>, _default_properties: &mut Vec<Property>, _rgraph: &RGraph) {
-    //     let mut i = 0;
-    //     while i < triples.len() {
-    //         if let [DInstruction::Communication(_)] = triples[i].instructions[..] {
-    //             let communication_triple = triples.remove(i); // TODO: swap_remove for performance?
-    //             assert_eq!(communication_triple.post_conditions.len(), 1);
-    //             assert_eq!(communication_triple.negative_post_conditions.len(), 0);
-    //             // can make index here if the number of triples is huge
-    //             for triple in triples.iter_mut() {
-    //                 // TODO: integreate with the ordered_communication heuristic?
-    //                 if triple.instructions.iter().any(|x| matches!(x, DInstruction::Communication(_))) {
-    //                     continue;
-    //                 }
-
-    //                 if triple.pre_conditions.contains(&communication_triple.post_conditions[0]) {
-    //                     triple.pre_conditions.extend(communication_triple.pre_conditions.clone());
-    //                     triple.pre_conditions.retain(|x| x != &communication_triple.post_conditions[0]);
-    //                     triple.post_conditions.push(communication_triple.post_conditions[0]);
-    //                     triple.instructions.insert(0, communication_triple.instructions[0]);
-    //                 }
-    //             }
-    //         } else {
-    //             i += 1
-    //         }
-    //     }
-    // }
+    pub struct OrderedCommunication;
+
+    impl HeuristicPass for OrderedCommunication {
+        fn apply(&self, triples: &mut Vec<HoareTriple>, default_properties: &mut Vec<Property>, rgraph: &RGraph) {
+            for triple in triples.iter_mut() {
+                if triple.kind == HoareTripleKind::Communication {
+                    if let Property::HasTensor(tensor_id, _) = triple.post_conditions[0] {
+                        triple.pre_conditions.push(Property::AllowCommunication(tensor_id));
+                        for i in 0..=tensor_id.0 {
+                            if rgraph[RTensorId(i)].communicatable {
+                                triple.negative_post_conditions.push(Property::AllowCommunication(RTensorId(i)));
+                            }
+                        }
+                        default_properties.push(Property::AllowCommunication(tensor_id));
+                    } else {
+                        unreachable!();
+                    }
+                }
+            }
+        }
+    }
+
+    // placeholders must happen in order, each one unlocking the next
+    pub struct OrderedPlaceholderChain;
+
+    impl HeuristicPass for OrderedPlaceholderChain {
+        fn apply(&self, triples: &mut Vec<HoareTriple>, default_properties: &mut Vec<Property>, _rgraph: &RGraph) {
+            for triple in triples.iter_mut() {
+                if let HoareTripleKind::Placeholder(placeholder_id) = triple.kind {
+                    triple.pre_conditions.push(Property::AllowPlaceholder(placeholder_id));
+                    triple.post_conditions.push(Property::AllowPlaceholder(placeholder_id + 1));
+                    triple.negative_post_conditions.push(Property::AllowPlaceholder(placeholder_id));
+                }
+            }
+            default_properties.push(Property::AllowPlaceholder(PlaceholderId(0)));
+        }
+    }
+
+    // get_attrs must happen in order, each one unlocking the next
+    pub struct OrderedGetAttrChain;
+
+    impl HeuristicPass for OrderedGetAttrChain {
+        fn apply(&self, triples: &mut Vec<HoareTriple>, default_properties: &mut Vec<Property>, _rgraph: &RGraph) {
+            for triple in triples.iter_mut() {
+                if let HoareTripleKind::GetAttr(parameter_id) = triple.kind {
+                    triple.pre_conditions.push(Property::AllowGetAttr(parameter_id));
+                    triple.post_conditions.push(Property::AllowGetAttr(parameter_id + 1));
+                    triple.negative_post_conditions.push(Property::AllowGetAttr(parameter_id));
+                }
+            }
+            default_properties.push(Property::AllowGetAttr(ParameterId(0)));
+        }
+    }
+
+    // fuse a standalone communication triple into every non-communication triple that consumes its
+    // result (communication triples are never fused into each other), so the
+    // collective is launched as part of its consumer instead of as a separate step (letting the
+    // search overlap/coalesce communication with the computation that needs it); must run after
+    // `OrderedCommunication`, so the ordering constraint it stamps onto the communication triple
+    // (pre/negative_post AllowCommunication properties) is preserved by being spliced into the
+    // consumer rather than discarded along with the triple it came from
+    pub struct FuseCommunicationForward;
+
+    impl HeuristicPass for FuseCommunicationForward {
+        fn apply(&self, triples: &mut Vec<HoareTriple>, _default_properties: &mut Vec<Property>, _rgraph: &RGraph) {
+            let mut i = 0;
+            while i < triples.len() {
+                if triples[i].kind == HoareTripleKind::Communication {
+                    let communication_triple = triples.remove(i); // TODO: swap_remove for performance?
+                    assert_eq!(communication_triple.post_conditions.len(), 1);
+
+                    // can index by pre-condition here if the number of triples is huge
+                    for triple in triples.iter_mut() {
+                        if triple.kind == HoareTripleKind::Communication {
+                            continue;
+                        }
+
+                        if triple.pre_conditions.contains(&communication_triple.post_conditions[0]) {
+                            triple.pre_conditions.extend(communication_triple.pre_conditions.iter().cloned());
+                            triple.pre_conditions.retain(|p| *p != communication_triple.post_conditions[0]);
+                            triple.post_conditions.push(communication_triple.post_conditions[0]);
+                            triple.negative_post_conditions.extend(communication_triple.negative_post_conditions.iter().cloned());
+                            triple.instruction = format!("{}, {}", communication_triple.instruction, triple.instruction);
+
+                            let communication_codegen = communication_triple.codegen.clone();
+                            let op_codegen = triple.codegen.clone();
+                            triple.codegen = Rc::new(move |ctx| {
+                                communication_codegen(ctx)?;
+                                op_codegen(ctx)
+                            });
+
+                            let communication_profile = communication_triple.profile.clone();
+                            let op_profile = triple.profile.clone();
+                            triple.profile = Rc::new(move |ctx| {
+                                let (communication_forward, communication_backward) = communication_profile(ctx);
+                                let (op_forward, op_backward) = op_profile(ctx);
+                                (add_profiles(communication_forward, op_forward), add_profiles(communication_backward, op_backward))
+                            });
+                        }
+                    }
+                } else {
+                    i += 1
+                }
+            }
+        }
+    }
+
+    fn add_profiles(a: Profile, b: Profile) -> Profile {
+        Profile {
+            flops: a.flops + b.flops,
+            all_reduce: a.all_reduce + b.all_reduce,
+            all_gather: a.all_gather + b.all_gather,
+            all_to_all: a.all_to_all + b.all_to_all,
+            reduce_scatter: a.reduce_scatter + b.reduce_scatter,
+        }
+    }
+
+    // the order matters: `OrderedCommunication` must run while communication triples are still
+    // their own triples, so `FuseCommunicationForward` has something real to match on and can
+    // carry the ordering constraint it stamped into the consumer it fuses into
+    pub fn default_passes() -> Vec<Box<dyn HeuristicPass>> {
+        vec![
+            Box::new(ComputeOnlyOnce),
+            Box::new(OrderedPlaceholderChain),
+            Box::new(OrderedGetAttrChain),
+            Box::new(OrderedCommunication),
+            Box::new(FuseCommunicationForward),
+        ]
+    }
+
+    pub fn run_passes(passes: &[Box<dyn HeuristicPass>], triples: &mut Vec<HoareTriple>, default_properties: &mut Vec<Property>, rgraph: &RGraph) {
+        for pass in passes {
+            pass.apply(triples, default_properties, rgraph);
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn dummy_triple(pre_conditions: SVec<Property, 4>, post_conditions: SVec<Property>, kind: HoareTripleKind) -> HoareTriple {
+            HoareTriple {
+                pre_conditions,
+                post_conditions,
+                negative_post_conditions: vec![],
+                instruction: "test".to_string(),
+                kind,
+                codegen: Rc::new(|_ctx| Ok(())),
+                profile: Rc::new(|_ctx| Default::default()),
+            }
+        }
+
+        // a fixed-point closure over pre/post conditions, mirroring what the search explores
+        fn reachable_properties(triples: &[HoareTriple], initial_properties: &[Property]) -> BTreeSet<Property> {
+            let mut reached: BTreeSet<Property> = initial_properties.iter().cloned().collect();
+            loop {
+                let mut changed = false;
+                for triple in triples {
+                    if triple.pre_conditions.iter().all(|p| reached.contains(p)) {
+                        for p in triple.post_conditions.iter() {
+                            changed |= reached.insert(*p);
+                        }
+                    }
+                }
+                if !changed {
+                    break;
+                }
+            }
+            reached
+        }
+
+        #[test]
+        fn fuse_communication_forward_preserves_reachability() {
+            let input_tensor_id = RTensorId(0);
+            let output_tensor_id = RTensorId(1);
+
+            let unfused_triples = vec![
+                dummy_triple(
+                    smallvec![Property::identity(input_tensor_id)],
+                    smallvec![Property::gather(input_tensor_id, 0)],
+                    HoareTripleKind::Communication
+                ),
+                dummy_triple(
+                    smallvec![Property::gather(input_tensor_id, 0)],
+                    smallvec![Property::identity(output_tensor_id)],
+                    HoareTripleKind::Computation(OpId(0))
+                ),
+            ];
+
+            let mut fused_triples = unfused_triples.clone();
+            let mut default_properties = vec![];
+            FuseCommunicationForward.apply(&mut fused_triples, &mut default_properties, &RGraph::default());
+
+            // the standalone communication triple should have been folded into its only consumer
+            assert_eq!(fused_triples.len(), 1);
+
+            let initial_properties = [Property::identity(input_tensor_id)];
+            assert_eq!(
+                reachable_properties(&unfused_triples, &initial_properties),
+                reachable_properties(&fused_triples, &initial_properties)
+            );
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Collective {
+    AllGather,
+    AllReduce,
+    ReduceScatter,
+    AllToAll,
 }
 
 #[derive(Debug)]
 struct ClusterInfo {
     device_flops: Vec<f64>,
-    all_reduce_bandwidth: f64,
-    all_gather_bandwidth: f64,
-    all_to_all_bandwidth: f64,
-    reduce_scatter_bandwidth: f64,
+
+    // ring alpha-beta model: alpha is the fixed per-message latency, beta is the
+    // per-byte time (inverse bandwidth), both specific to each collective
+    all_gather_alpha: f64,
+    all_gather_beta: f64,
+    all_reduce_alpha: f64,
+    all_reduce_beta: f64,
+    reduce_scatter_alpha: f64,
+    reduce_scatter_beta: f64,
+    all_to_all_alpha: f64,
+    all_to_all_beta: f64,
 }
 
 impl ClusterInfo {
     fn n_devices(&self) -> usize {
         self.device_flops.len()
     }
+
+    // a faster device can profitably hold a larger slice of a gathered dimension, so the
+    // default split is proportional to each device's share of total compute throughput,
+    // rather than even across devices
+    fn default_sharding_ratios(&self) -> Vec<f64> {
+        let total_flops: f64 = self.device_flops.iter().sum();
+        self.device_flops.iter().map(|flops| flops / total_flops).collect()
+    }
+
+    // standard ring collective costs: a latency term proportional to the number of ring
+    // steps, plus a bandwidth term proportional to the fraction of the buffer each step
+    // actually has to move
+    fn collective_cost(&self, kind: Collective, bytes: f64) -> f64 {
+        if bytes == 0. {
+            return 0.;
+        }
+
+        let p = self.n_devices() as f64;
+        let (alpha, beta) = match kind {
+            Collective::AllGather => (self.all_gather_alpha, self.all_gather_beta),
+            Collective::AllReduce => (self.all_reduce_alpha, self.all_reduce_beta),
+            Collective::ReduceScatter => (self.reduce_scatter_alpha, self.reduce_scatter_beta),
+            Collective::AllToAll => (self.all_to_all_alpha, self.all_to_all_beta),
+        };
+
+        match kind {
+            Collective::AllGather | Collective::ReduceScatter | Collective::AllToAll =>
+                (p - 1.) * alpha + (p - 1.) / p * bytes * beta,
+            Collective::AllReduce =>
+                2. * (p - 1.) * alpha + 2. * (p - 1.) / p * bytes * beta,
+        }
+    }
 }
 
 fn sharding_round(full_length: usize, sharding_ratios: &[f64]) -> Vec<usize> {